@@ -1,13 +1,19 @@
+use argon2::password_hash::PasswordHash;
+use argon2::{Argon2, PasswordVerifier};
 use axum::body::Body;
-use axum::extract::State;
-use axum::http::{Request, Response, StatusCode, header::AUTHORIZATION};
-use axum::{Extension, Json};
-use axum::middleware::Next;
-use chrono::{Duration, Utc};
+use axum::extract::{FromRequestParts, Query, State};
+use axum::http::request::Parts;
+use axum::http::{Request, StatusCode, header::AUTHORIZATION};
+use axum::{async_trait, Json};
+use chrono::{DateTime, Duration, Utc};
 use jsonwebtoken::{encode, decode, EncodingKey, DecodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::mail::Mailer;
 use crate::state::AppState;
+use crate::user::{EmailVerification, RefreshToken, User, UserLookup};
 
 // This is a JWT claim.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,11 +21,12 @@ pub struct Claims {
     sub: String,     // Subject (user ID)
     exp: i64,        // Expiration time
     iat: i64,        // Issued at time
+    jti: String,     // Unique token ID, used to revoke this specific token
 }
 
 impl Claims {
-    pub fn new(sub: String, exp: i64, iat: i64) -> Self {
-        Self { sub, exp, iat }
+    pub fn new(sub: String, exp: i64, iat: i64, jti: String) -> Self {
+        Self { sub, exp, iat, jti }
     }
 
     pub fn sub(&self) -> &str {
@@ -33,26 +40,126 @@ impl Claims {
     pub fn iat(&self) -> i64 {
         self.iat
     }
+
+    pub fn jti(&self) -> &str {
+        &self.jti
+    }
 }
 
-#[derive(Deserialize, Debug)]
+// Lets handlers take `claims: Claims` directly as an argument instead of
+// relying on a middleware layer to have stashed it in the request
+// extensions first — authentication becomes part of the handler's type
+// signature rather than something a forgotten `.layer()` can silently skip.
+#[async_trait]
+impl FromRequestParts<AppState> for Claims {
+    type Rejection = (StatusCode, Json<Value>);
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let auth_header = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|header| header.to_str().ok())
+            .ok_or_else(|| (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({ "error": "Missing authorization header" }))
+            ))?;
+
+        if !auth_header.starts_with("Bearer ") {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                Json(json!({ "error": "Invalid authorization header format" }))
+            ));
+        }
+
+        let token = &auth_header[7..];
+        let session = Session::new(state.clone());
+        session.verify_token(token)
+            .await
+            .map_err(Into::into)
+    }
+}
+
+// Like `Claims`, but additionally rejects the request unless the user's
+// email address has been confirmed. Use this in place of `Claims` on routes
+// that must not be reachable by an unverified account.
+pub struct VerifiedClaims(Claims);
+
+impl VerifiedClaims {
+    pub fn claims(&self) -> &Claims {
+        &self.0
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for VerifiedClaims {
+    type Rejection = (StatusCode, Json<Value>);
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let claims = Claims::from_request_parts(parts, state).await?;
+
+        let user_id = Uuid::parse_str(claims.sub())
+            .map_err(|_| -> (StatusCode, Json<Value>) { Error::InvalidTokenFormat.into() })?;
+
+        let user = User::find_user(UserLookup::ByUuid(user_id), State(state.clone()))
+            .await
+            .map_err(|_| (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({ "error": "User not found" })),
+            ))?;
+
+        if !user.email_verified() {
+            return Err((
+                StatusCode::FORBIDDEN,
+                Json(json!({ "error": "Email address is not verified", "code": "email_not_verified" })),
+            ));
+        }
+
+        Ok(VerifiedClaims(claims))
+    }
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
 pub struct LoginRequest {
     username: String,
     password: String,
 }
 
-#[derive(Serialize)]
+#[derive(Deserialize, Debug)]
+pub struct RefreshRequest {
+    refresh_token: String,
+}
+
+#[derive(Serialize, ToSchema)]
 pub struct LoginResponse {
     token: String,
     token_type: String,
     token_expires: i64,
+    refresh_token: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct LogoutResponse {
     message: String,
 }
 
+// Mirrors the JSON shape produced by `impl From<Error> for (StatusCode, Json<Value>)`,
+// so error responses show up in the generated OpenAPI spec.
+#[derive(Serialize, ToSchema)]
+pub struct ErrorResponse {
+    error: String,
+    code: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct VerifyConfirmQuery {
+    token: String,
+}
+
+#[derive(Serialize)]
+pub struct VerifyRequestResponse {
+    token: String,
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Invalid credentials")]
@@ -67,6 +174,16 @@ pub enum Error {
     InvalidTokenFormat,
     #[error("Token has been revoked")]
     TokenRevoked,
+    #[error("Refresh token is invalid")]
+    RefreshTokenInvalid,
+    #[error("Refresh token has expired")]
+    RefreshTokenExpired,
+    #[error("Account is locked")]
+    AccountLocked,
+    #[error("Email verification token is invalid")]
+    EmailVerificationInvalid,
+    #[error("Email verification token has expired")]
+    EmailVerificationExpired,
 }
 
 impl From<Error> for (StatusCode, Json<Value>) {
@@ -76,6 +193,9 @@ impl From<Error> for (StatusCode, Json<Value>) {
             Error::TokenCreation | Error::TokenValidation => StatusCode::INTERNAL_SERVER_ERROR,
             Error::InvalidTokenFormat => StatusCode::BAD_REQUEST,
             Error::TokenRevoked => StatusCode::FORBIDDEN,
+            Error::RefreshTokenInvalid | Error::RefreshTokenExpired => StatusCode::UNAUTHORIZED,
+            Error::AccountLocked => StatusCode::LOCKED,
+            Error::EmailVerificationInvalid | Error::EmailVerificationExpired => StatusCode::BAD_REQUEST,
         };
         (status, Json(json!({
             "error": error.to_string(),
@@ -86,11 +206,80 @@ impl From<Error> for (StatusCode, Json<Value>) {
                 Error::TokenCreation => "token_creation_failed",
                 Error::InvalidTokenFormat => "invalid_format",
                 Error::TokenRevoked => "token_revoked",
+                Error::RefreshTokenInvalid => "refresh_token_invalid",
+                Error::RefreshTokenExpired => "refresh_token_expired",
+                Error::AccountLocked => "account_locked",
+                Error::EmailVerificationInvalid => "email_verification_invalid",
+                Error::EmailVerificationExpired => "email_verification_expired",
             }
         })))
     }
 }
 
+// Refresh tokens live much longer than the access JWT they back.
+const REFRESH_TOKEN_LIFETIME_DAYS: i64 = 30;
+
+// Email verification links are short-lived since they're a one-time proof
+// of inbox ownership, not a standing credential.
+const EMAIL_VERIFICATION_LIFETIME_HOURS: i64 = 24;
+
+// A pre-computed Argon2 PHC hash with no corresponding real password. When a
+// login targets an unknown email (or a user with no password set), we still
+// run a full Argon2 verify against this so the unknown-user path costs the
+// same time as a known-user, wrong-password path — otherwise the missing
+// hash work is a timing oracle for which emails are registered.
+const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$ZHVtbXlzYWx0Zm9ydGltaW5n$are/8MBt7TC43S6iLH8QEb/K9Q9vu02U5UhTGBvQ9Dg";
+
+// Absolute ceiling on an account lockout, no matter how many times the
+// exponential backoff has doubled.
+const MAX_LOCKOUT_SECONDS: i64 = 24 * 60 * 60;
+
+// A revoked access token, keyed by its `jti` rather than the token itself.
+// Backed by Postgres so revocation survives restarts and is visible to
+// every server instance, instead of living in a per-process HashMap.
+struct RevokedToken;
+
+impl RevokedToken {
+    async fn insert(jti: Uuid, expires_at: DateTime<Utc>, state: State<AppState>) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO revoked_tokens (jti, expires_at)
+            VALUES ($1, $2)
+            ON CONFLICT (jti) DO NOTHING
+            "#,
+            jti,
+            expires_at
+        )
+            .execute(state.db()?)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn is_revoked(jti: Uuid, state: State<AppState>) -> Result<bool, sqlx::Error> {
+        let row = sqlx::query!(
+            "SELECT jti FROM revoked_tokens WHERE jti = $1",
+            jti
+        )
+            .fetch_optional(state.db()?)
+            .await?;
+
+        Ok(row.is_some())
+    }
+}
+
+// Periodically run from `main` to keep the `revoked_tokens` table from
+// growing unbounded; entries past their own token's expiry are dead weight.
+pub async fn cleanup_expired_revoked_tokens(state: AppState) -> Result<(), Error> {
+    sqlx::query!("DELETE FROM revoked_tokens WHERE expires_at < NOW()")
+        .execute(state.db().map_err(|_| Error::TokenValidation)?)
+        .await
+        .map_err(|_| Error::TokenValidation)?;
+
+    Ok(())
+}
+
 struct Session {
     state: AppState,
     claim: Option<Claims>
@@ -105,18 +294,203 @@ impl Session {
     }
 
     pub async fn login(&self, credentials: LoginRequest) -> Result<LoginResponse, Error> {
-        // TODO: Replace with actual database lookup and password verification
-        if credentials.username == "admin" && credentials.password == "password" {
-            let (token, expires_at) = self.create_token(credentials.username)?;
-
-            Ok(LoginResponse {
-                token,
-                token_type: "Bearer".to_string(),
-                token_expires: expires_at,
-            })
-        } else {
-            Err(Error::InvalidCredentials)
+        // Look up by email and verify the submitted password regardless of
+        // whether the user was found, so the response is identical either
+        // way and doesn't leak which emails are registered.
+        let user = User::find_user(
+            UserLookup::ByEmail(credentials.username.clone()),
+            State(self.state.clone()),
+        )
+            .await
+            .ok();
+
+        // Always run a full Argon2 verify, even when there's no real hash to
+        // check against, before making any decision based on account state —
+        // so a nonexistent email, a wrong password, and a locked account all
+        // pay the same Argon2 cost. A wrong password for a registered account
+        // additionally records the failed attempt in the database.
+        let password_matches = match user.as_ref().and_then(|user| user.password_hash().ok()) {
+            Some(hash) => Self::verify_password(&credentials.password, hash),
+            None => {
+                Self::verify_password(&credentials.password, DUMMY_PASSWORD_HASH);
+                false
+            }
+        };
+
+        if let Some(locked_until) = user.as_ref().and_then(|user| user.locked_until().ok()) {
+            if locked_until > Utc::now() {
+                return Err(Error::AccountLocked);
+            }
+        }
+
+        if !password_matches {
+            if let Some(user_id) = user.and_then(|user| user.uuid().ok()) {
+                self.register_failed_login(user_id).await?;
+            }
+            return Err(Error::InvalidCredentials);
+        }
+
+        let user_id = user
+            .and_then(|user| user.uuid().ok())
+            .ok_or(Error::InvalidCredentials)?;
+
+        User::reset_login_state(user_id, State(self.state.clone()))
+            .await
+            .map_err(|_| Error::TokenCreation)?;
+
+        let (token, expires_at) = self.create_token(user_id.to_string())?;
+        let refresh_token = self.issue_refresh_token(user_id).await?;
+
+        Ok(LoginResponse {
+            token,
+            token_type: "Bearer".to_string(),
+            token_expires: expires_at,
+            refresh_token,
+        })
+    }
+
+    // Bumps the failed-attempt counter and, once it crosses the configured
+    // threshold, locks the account for an exponentially increasing backoff
+    // window (doubling with each subsequent lockout).
+    async fn register_failed_login(&self, user_id: Uuid) -> Result<(), Error> {
+        let attempts = User::increment_failed_attempts(user_id, State(self.state.clone()))
+            .await
+            .map_err(|_| Error::TokenCreation)?;
+
+        let threshold = self.state.lockout_threshold();
+        if threshold > 0 && attempts % threshold == 0 {
+            let lockouts_triggered = attempts / threshold;
+            // Cap the exponent so this can never overflow, and cap the
+            // resulting duration at a sane ceiling rather than letting
+            // doubling run away indefinitely.
+            let exponent = (lockouts_triggered - 1).clamp(0, 30) as u32;
+            let lock_seconds = self.state.lockout_base_seconds()
+                .saturating_mul(2i64.saturating_pow(exponent))
+                .min(MAX_LOCKOUT_SECONDS);
+            let locked_until = Utc::now() + Duration::seconds(lock_seconds);
+
+            User::lock_until(user_id, locked_until, State(self.state.clone()))
+                .await
+                .map_err(|_| Error::TokenCreation)?;
         }
+
+        Ok(())
+    }
+
+    fn verify_password(password: &str, password_hash: &str) -> bool {
+        let Ok(parsed_hash) = PasswordHash::new(password_hash) else {
+            return false;
+        };
+
+        Argon2::default()
+            .verify_password(password.as_bytes(), &parsed_hash)
+            .is_ok()
+    }
+
+    // Mints a new refresh token for `user_id`, persisting only its hash.
+    async fn issue_refresh_token(&self, user_id: Uuid) -> Result<String, Error> {
+        let (raw_token, token_hash) = RefreshToken::generate();
+        let expires_at = Utc::now() + Duration::days(REFRESH_TOKEN_LIFETIME_DAYS);
+
+        RefreshToken::create(user_id, &token_hash, expires_at, State(self.state.clone()))
+            .await
+            .map_err(|_| Error::TokenCreation)?;
+
+        Ok(raw_token)
+    }
+
+    // Rotates a presented refresh token: the old one is revoked and a new
+    // access/refresh pair is issued. A refresh token that is presented
+    // after it has already been revoked is treated as token theft, which
+    // revokes every refresh token belonging to that user.
+    pub async fn refresh(&self, presented_token: &str) -> Result<LoginResponse, Error> {
+        let token_hash = RefreshToken::hash(presented_token);
+        let existing = RefreshToken::find_by_hash(&token_hash, State(self.state.clone()))
+            .await
+            .map_err(|_| Error::RefreshTokenInvalid)?;
+        let user_id = existing.user_id().map_err(|_| Error::RefreshTokenInvalid)?;
+        let token_id = existing.uuid().map_err(|_| Error::RefreshTokenInvalid)?;
+
+        if existing.revoked() {
+            let _ = RefreshToken::revoke_all_for_user(user_id, State(self.state.clone())).await;
+            return Err(Error::RefreshTokenInvalid);
+        }
+
+        let expires_at = existing.expires_at().map_err(|_| Error::RefreshTokenInvalid)?;
+        if expires_at < Utc::now() {
+            return Err(Error::RefreshTokenExpired);
+        }
+
+        // Claiming the rotation is a single conditional UPDATE, so only one
+        // of two concurrent requests presenting the same token can win it.
+        // Losing (or finding it already revoked) means this token has been
+        // presented before — that's reuse of a rotated-out token, i.e.
+        // theft, so burn every token the user has outstanding.
+        let claimed = RefreshToken::revoke_if_active(token_id, State(self.state.clone()))
+            .await
+            .map_err(|_| Error::TokenCreation)?;
+        if !claimed {
+            let _ = RefreshToken::revoke_all_for_user(user_id, State(self.state.clone())).await;
+            return Err(Error::RefreshTokenInvalid);
+        }
+
+        let (token, token_expires) = self.create_token(user_id.to_string())?;
+        let refresh_token = self.issue_refresh_token(user_id).await?;
+
+        Ok(LoginResponse {
+            token,
+            token_type: "Bearer".to_string(),
+            token_expires,
+            refresh_token,
+        })
+    }
+
+    // Generates and stores a single-use email verification token for the
+    // authenticated user, and fires it off to the configured `Mailer`.
+    pub async fn request_email_verification(&self, claims: &Claims) -> Result<String, Error> {
+        let user_id = Uuid::parse_str(claims.sub()).map_err(|_| Error::InvalidTokenFormat)?;
+        let user = User::find_user(UserLookup::ByUuid(user_id), State(self.state.clone()))
+            .await
+            .map_err(|_| Error::InvalidCredentials)?;
+
+        let (raw_token, token_hash) = EmailVerification::generate();
+        let expires_at = Utc::now() + Duration::hours(EMAIL_VERIFICATION_LIFETIME_HOURS);
+
+        EmailVerification::create(user_id, &token_hash, expires_at, State(self.state.clone()))
+            .await
+            .map_err(|_| Error::TokenCreation)?;
+
+        if let Ok(email) = user.email() {
+            self.state.mailer().send_verification_email(email, &raw_token).await;
+        }
+
+        Ok(raw_token)
+    }
+
+    // Confirms a presented email verification token: flips the user's
+    // `email_verified` flag and burns the single-use token.
+    pub async fn confirm_email_verification(&self, presented_token: &str) -> Result<(), Error> {
+        let token_hash = EmailVerification::hash(presented_token);
+        let verification = EmailVerification::find_by_hash(&token_hash, State(self.state.clone()))
+            .await
+            .map_err(|_| Error::EmailVerificationInvalid)?;
+
+        let expires_at = verification.expires_at().map_err(|_| Error::EmailVerificationInvalid)?;
+        if expires_at < Utc::now() {
+            let _ = EmailVerification::delete(&token_hash, State(self.state.clone())).await;
+            return Err(Error::EmailVerificationExpired);
+        }
+
+        let user_id = verification.user_id().map_err(|_| Error::EmailVerificationInvalid)?;
+        User::verify_email(user_id, State(self.state.clone()))
+            .await
+            .map_err(|_| Error::TokenCreation)?;
+
+        EmailVerification::delete(&token_hash, State(self.state.clone()))
+            .await
+            .map_err(|_| Error::TokenCreation)?;
+
+        Ok(())
     }
 
     pub fn create_token(&self, user_id: String) -> Result<(String, i64), Error> {
@@ -127,6 +501,7 @@ impl Session {
             sub: user_id,
             exp: expires_at.timestamp(),
             iat: now.timestamp(),
+            jti: Uuid::new_v4().to_string(),
         };
 
         let token = encode(
@@ -139,13 +514,7 @@ impl Session {
     }
 
     // Verify a JWT token
-    pub fn verify_token(&self, token: &str) -> Result<Claims, Error> {
-        // Step 1: Check if the token is blacklisted
-        let blacklist = self.state.token_blacklist.lock().unwrap();
-        if blacklist.contains_key(token) {
-            return Err(Error::TokenRevoked);
-        }
-
+    pub async fn verify_token(&self, token: &str) -> Result<Claims, Error> {
         let validation = Validation::default();
         match decode::<Claims>(
             token,
@@ -160,6 +529,15 @@ impl Session {
                     return Err(Error::TokenExpired);
                 }
 
+                // Check if this specific token has been revoked
+                let jti = Uuid::parse_str(&claims.jti).map_err(|_| Error::InvalidTokenFormat)?;
+                let revoked = RevokedToken::is_revoked(jti, State(self.state.clone()))
+                    .await
+                    .map_err(|_| Error::TokenValidation)?;
+                if revoked {
+                    return Err(Error::TokenRevoked);
+                }
+
                 Ok(claims)
             }
             Err(e) => match e.kind() {
@@ -170,7 +548,7 @@ impl Session {
         }
     }
 
-    pub fn decode_token(&self, req: &Request<Body>) -> Result<(String, Claims), (StatusCode, Json<Value>)> {
+    pub async fn decode_token(&self, req: &Request<Body>) -> Result<(String, Claims), (StatusCode, Json<Value>)> {
         // 1. Extract the Authorization header
         let auth_header = req
             .headers()
@@ -192,6 +570,7 @@ impl Session {
         // 3. Extract and verify the token
         let token = &auth_header[7..];
         let claims = self.verify_token(token)
+            .await
             .map_err(|e: Error| { // Explicitly handle the error conversion
                 let (status, json) = e.into();
                 (status, json)
@@ -200,20 +579,34 @@ impl Session {
         Ok((token.to_string(), claims))
     }
 
-    pub fn invalidate_token(&self, token: &str) -> Result<(), Error> {
-        let claims = self.verify_token(token)?;
-        let mut blacklist = self.state.token_blacklist.lock().unwrap();
-        blacklist.insert(token.to_string(), claims.exp);
-        Ok(())
-    }
+    pub async fn invalidate_token(&self, token: &str) -> Result<(), Error> {
+        let claims = self.verify_token(token).await?;
+
+        let jti = Uuid::parse_str(&claims.jti).map_err(|_| Error::InvalidTokenFormat)?;
+        let expires_at = DateTime::from_timestamp(claims.exp, 0).ok_or(Error::TokenValidation)?;
+        RevokedToken::insert(jti, expires_at, State(self.state.clone()))
+            .await
+            .map_err(|_| Error::TokenCreation)?;
+
+        // Logging out also ends any refresh tokens outstanding for this user.
+        if let Ok(user_id) = Uuid::parse_str(claims.sub()) {
+            let _ = RefreshToken::revoke_all_for_user(user_id, State(self.state.clone())).await;
+        }
 
-    pub fn cleanup_blacklist(&self) {
-        let mut blacklist = self.state.token_blacklist.lock().unwrap();
-        let mut now = Utc::now().timestamp();
-        blacklist.retain(|_, exp| exp > &mut now);
+        Ok(())
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/login",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Login succeeded", body = LoginResponse),
+        (status = 401, description = "Invalid credentials", body = ErrorResponse),
+        (status = 423, description = "Account is locked", body = ErrorResponse),
+    ),
+)]
 pub async fn handle_login(
     State(state): State<AppState>,
     Json(login_req): Json<LoginRequest>,
@@ -225,40 +618,67 @@ pub async fn handle_login(
         .map_err(Into::into)
 }
 
+#[utoipa::path(
+    get,
+    path = "/logout",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Successfully logged out", body = LogoutResponse),
+        (status = 401, description = "Missing, invalid, or expired token", body = ErrorResponse),
+    ),
+)]
 pub async fn handle_logout(
     State(state): State<AppState>,
     req: Request<Body>,
 ) -> Result<Json<LogoutResponse>, (StatusCode, Json<Value>)> {
     let session = Session::new(state);
     let (token, _claims) = session.decode_token(&req)
+        .await
         .map_err(|e| e)?;
 
     // Invalidate the token
     session.invalidate_token(&token)
+        .await
         .map_err(|e: Error| {
             let (status, json) = e.into();
             (status, json)
         })?;
 
-    // Clean up expired tokens while we're here
-    session.cleanup_blacklist();
-
     Ok(Json(LogoutResponse {
         message: "Successfully logged out".to_string(),
     }))
 }
 
-pub async fn middleware(
+pub async fn handle_refresh(
     State(state): State<AppState>,
-    mut req: Request<Body>,
-    next: Next,
-) -> Result<Response<Body>, (StatusCode, Json<Value>)> {
+    Json(refresh_req): Json<RefreshRequest>,
+) -> Result<Json<LoginResponse>, (StatusCode, Json<Value>)> {
     let session = Session::new(state);
-    let (_token, claims) = session.decode_token(&req)?;
+    session.refresh(&refresh_req.refresh_token)
+        .await
+        .map(Json)
+        .map_err(Into::into)
+}
 
-    // Store the verified claims for the route handler
-    req.extensions_mut().insert(claims);
+pub async fn handle_verify_request(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<VerifyRequestResponse>, (StatusCode, Json<Value>)> {
+    let session = Session::new(state);
+    session.request_email_verification(&claims)
+        .await
+        .map(|token| Json(VerifyRequestResponse { token }))
+        .map_err(Into::into)
+}
 
-    // Continue to the route handler
-    Ok(next.run(req).await)
+pub async fn handle_verify_confirm(
+    State(state): State<AppState>,
+    Query(query): Query<VerifyConfirmQuery>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let session = Session::new(state);
+    session.confirm_email_verification(&query.token)
+        .await
+        .map(|_| Json(json!({ "message": "Email verified" })))
+        .map_err(Into::into)
 }
+
@@ -0,0 +1,21 @@
+use async_trait::async_trait;
+
+// Abstracts sending transactional email so a real provider (SMTP, an API-based
+// sender, ...) can be wired in later without touching the callers that just
+// need a verification message sent.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send_verification_email(&self, to: &str, token: &str);
+}
+
+// Default `Mailer` used until a real provider is wired in: just logs what
+// would have been sent.
+#[derive(Debug, Default, Clone)]
+pub struct LoggingMailer;
+
+#[async_trait]
+impl Mailer for LoggingMailer {
+    async fn send_verification_email(&self, to: &str, token: &str) {
+        println!("[mail] verification email for {to}: token={token}");
+    }
+}
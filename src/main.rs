@@ -3,18 +3,23 @@ use axum::{
     routing::{post, get},
     Router,
     response::Json,
-    extract::Extension,
 };
 
-use axum::middleware::from_fn_with_state;
 use serde_json::{Value, json};
 use serde::{Serialize, Deserialize};
 use tower_http::cors::CorsLayer;
 use std::sync::{Arc};
+use utoipa::{OpenApi, Modify, ToSchema};
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa_swagger_ui::SwaggerUi;
 
-use bss_backend::auth::{Claims, handle_login, handle_logout};
+use bss_backend::auth::{
+    ErrorResponse, LoginRequest, LoginResponse, LogoutResponse, VerifiedClaims,
+    handle_login, handle_logout, handle_refresh, handle_verify_confirm, handle_verify_request,
+};
 use bss_backend::state::AppState;
 use bss_backend::ping::handle_ping;
+use bss_backend::user::UserProfile;
 
 
 #[tokio::main]
@@ -26,22 +31,33 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             env::var("DATABASE_URL").expect("DATABASE_URL must be set"))
         .await?;
 
+    let cleanup_state = state.clone();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            ticker.tick().await;
+            if let Err(e) = bss_backend::auth::cleanup_expired_revoked_tokens(cleanup_state.clone()).await {
+                eprintln!("failed to clean up expired revoked tokens: {e}");
+            }
+        }
+    });
+
     let public_routes = Router::new()
         .route("/login", post(handle_login))
-        .route("/logout", get(handle_logout));
+        .route("/logout", get(handle_logout))
+        .route("/refresh", post(handle_refresh))
+        .route("/verify/confirm", get(handle_verify_confirm));
 
     let protected_routes = Router::new()
         .route("/api", post(api))
         .route("/ping", post(handle_ping))
-        .layer(from_fn_with_state(
-            state.clone(),
-            bss_backend::auth::middleware,
-        ));
+        .route("/verify/request", post(handle_verify_request));
 
     // This maps incoming URLs to the functions that will handle them.
     let app = Router::new()
         .merge(public_routes)
         .merge(protected_routes)
+        .merge(SwaggerUi::new("/docs").url("/openapi.json", ApiDoc::openapi()))
         .layer(CorsLayer::permissive()) // FIXME: This is insecure, don't use permissive in production
         .with_state(state);
 
@@ -51,21 +67,71 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct Testing {
     name: String,
     user: String,
 }
 
-// This is an example of a protected endpoint
+// This is an example of a protected endpoint. It additionally requires a
+// confirmed email address, since it stands in for a business-sensitive
+// action rather than a read-only one like `/ping`.
+#[utoipa::path(
+    post,
+    path = "/api",
+    security(("bearer_auth" = [])),
+    request_body = Value,
+    responses(
+        (status = 200, description = "Echoes the name back with the caller's subject", body = Testing),
+        (status = 403, description = "Email address is not verified", body = ErrorResponse),
+    ),
+)]
 async fn api(
-    Extension(claims): Extension<Claims>,
+    claims: VerifiedClaims,
     Json(body): Json<Value>,
 ) -> Json<Testing> {
     let name = body["name"].as_str().unwrap();  // FIXME: This WILL panic if the key is missing
     let response = Testing {
         name: name.to_string(),
-        user: claims.sub().to_string(),
+        user: claims.claims().sub().to_string(),
     };
     Json(response)
 }
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        bss_backend::auth::handle_login,
+        bss_backend::auth::handle_logout,
+        bss_backend::ping::handle_ping,
+        api,
+    ),
+    components(schemas(
+        LoginRequest,
+        LoginResponse,
+        LogoutResponse,
+        ErrorResponse,
+        Testing,
+        UserProfile,
+    )),
+    modifiers(&SecurityAddon),
+)]
+struct ApiDoc;
+
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "bearer_auth",
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .bearer_format("JWT")
+                        .build(),
+                ),
+            );
+        }
+    }
+}
@@ -1,7 +1,18 @@
 use axum::{Json};
 use serde_json::{Value};
+use crate::auth::Claims;
 
+#[utoipa::path(
+    post,
+    path = "/ping",
+    security(("bearer_auth" = [])),
+    request_body = Value,
+    responses(
+        (status = 200, description = "Echoes the request body back", body = Value),
+    ),
+)]
 pub async fn handle_ping(
+    _claims: Claims,
     Json(body): Json<Value>,
 ) -> Json<Value> {
     Json::from(body)
@@ -1,17 +1,38 @@
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::fmt;
+use std::sync::Arc;
 use core::time::Duration;
 use sqlx::PgPool;
 use sqlx::postgres::{PgPoolOptions};
+use crate::mail::{LoggingMailer, Mailer};
 
 // This application needs to keep a JWT secret key.
 // WARNING: This is sensitive information.
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct AppState {
     jwt_secret: Option<Arc<String>>,
-    token_blacklist: Arc<Mutex<HashMap<String, i64>>>,
     pool: Option<Arc<PgPool>>,
     uri: Option<Arc<String>>,
+    // Number of failed login attempts before an account is locked out.
+    lockout_threshold: i32,
+    // Base lockout duration; doubles for each subsequent lockout.
+    lockout_base_seconds: i64,
+    // How verification emails (and eventually other transactional mail) get
+    // delivered. Defaults to a logging no-op; swap in a real provider with
+    // `with_mailer` without touching any caller.
+    mailer: Arc<dyn Mailer>,
+}
+
+impl fmt::Debug for AppState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AppState")
+            .field("jwt_secret", &self.jwt_secret)
+            .field("pool", &self.pool)
+            .field("uri", &self.uri)
+            .field("lockout_threshold", &self.lockout_threshold)
+            .field("lockout_base_seconds", &self.lockout_base_seconds)
+            .field("mailer", &"<dyn Mailer>")
+            .finish()
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -28,9 +49,11 @@ impl AppState {
     pub fn new() -> Self {
         Self {
             jwt_secret: None,
-            token_blacklist: Arc::new(Mutex::new(HashMap::new())),
             pool: None,
             uri: None,
+            lockout_threshold: 5,
+            lockout_base_seconds: 60,
+            mailer: Arc::new(LoggingMailer),
         }
     }
 
@@ -46,8 +69,31 @@ impl AppState {
         }
     }
 
-    pub fn token_blacklist(&self) -> &Arc<Mutex<HashMap<String, i64>>> {
-        &self.token_blacklist
+    pub fn with_lockout_threshold(mut self, lockout_threshold: i32) -> Self {
+        self.lockout_threshold = lockout_threshold;
+        self
+    }
+
+    pub fn lockout_threshold(&self) -> i32 {
+        self.lockout_threshold
+    }
+
+    pub fn with_lockout_base_seconds(mut self, lockout_base_seconds: i64) -> Self {
+        self.lockout_base_seconds = lockout_base_seconds;
+        self
+    }
+
+    pub fn lockout_base_seconds(&self) -> i64 {
+        self.lockout_base_seconds
+    }
+
+    pub fn with_mailer(mut self, mailer: Arc<dyn Mailer>) -> Self {
+        self.mailer = mailer;
+        self
+    }
+
+    pub fn mailer(&self) -> Arc<dyn Mailer> {
+        self.mailer.clone()
     }
 
     pub async fn with_db_uri(mut self, uri: String) -> Result<Self, Error> {
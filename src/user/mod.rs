@@ -1,6 +1,14 @@
 use std::iter::Skip;
+use argon2::password_hash::rand_core::OsRng as ArgonOsRng;
+use argon2::password_hash::{PasswordHasher, SaltString};
+use argon2::Argon2;
 use axum::extract::State;
 use chrono::{DateTime, Utc};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
 use uuid::Uuid;
 use sqlx::PgPool;
 use validator::ValidateEmail;
@@ -31,6 +39,12 @@ pub enum Error {
     DatabaseError(#[from] sqlx::Error),
     #[error("User not found")]
     UserNotFound,
+    #[error("Refresh token not found")]
+    RefreshTokenNotFound,
+    #[error("Password hashing failed: {0}")]
+    PasswordHashError(String),
+    #[error("Email verification not found")]
+    EmailVerificationNotFound,
 }
 
 impl From<crate::state::Error> for Error {
@@ -194,11 +208,23 @@ impl User {
         }
     }
 
+    // Hashes a plaintext password into a PHC string using Argon2 with an
+    // OS-random salt. This is the only place a password should be hashed.
+    pub fn hash_password(password: &str) -> Result<String, Error> {
+        let salt = SaltString::generate(&mut ArgonOsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| Error::PasswordHashError(e.to_string()))
+    }
+
     pub async fn create(
         email: &str,
-        password_hash: &str,
+        password: &str,
         state: State<AppState>,
-    ) -> Result<User, sqlx::Error> {
+    ) -> Result<User, Error> {
+        let password_hash = Self::hash_password(password)?;
+
         let user = sqlx::query_as!(
             User,
             r#"
@@ -254,4 +280,356 @@ impl User {
 
         Ok(user)
     }
+
+    // Records a failed login attempt and returns the new attempt count, so
+    // the caller can decide whether the lockout threshold was crossed. The
+    // counter keeps running across lockouts (only a successful login resets
+    // it), so every `threshold` failures trigger another lockout and the
+    // count tells the caller how many lockouts have happened. A lockout
+    // window that has already lapsed is cleared here.
+    pub async fn increment_failed_attempts(user_id: Uuid, state: State<AppState>) -> Result<i32, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            UPDATE users
+            SET failed_login_attempts = failed_login_attempts + 1,
+                locked_until = CASE
+                    WHEN locked_until IS NOT NULL AND locked_until <= NOW() THEN NULL
+                    ELSE locked_until
+                END
+            WHERE id = $1
+            RETURNING failed_login_attempts
+            "#,
+            user_id
+        )
+            .fetch_one(state.db()?)
+            .await?;
+
+        Ok(row.failed_login_attempts)
+    }
+
+    pub async fn lock_until(user_id: Uuid, locked_until: DateTime<Utc>, state: State<AppState>) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE users SET locked_until = $2 WHERE id = $1",
+            user_id,
+            locked_until
+        )
+            .execute(state.db()?)
+            .await?;
+
+        Ok(())
+    }
+
+    // Clears lockout state on a successful login and stamps `last_login`.
+    pub async fn reset_login_state(user_id: Uuid, state: State<AppState>) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET failed_login_attempts = 0, locked_until = NULL, last_login = NOW()
+            WHERE id = $1
+            "#,
+            user_id
+        )
+            .execute(state.db()?)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn verify_email(user_id: Uuid, state: State<AppState>) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE users SET email_verified = true WHERE id = $1",
+            user_id
+        )
+            .execute(state.db()?)
+            .await?;
+
+        Ok(())
+    }
+
+    // Public-facing projection of a `User`, stripped of the password hash and
+    // lockout bookkeeping. Exists so routes can hand back a user's identity
+    // without leaking internal fields.
+    pub fn profile(&self) -> Result<UserProfile, Error> {
+        Ok(UserProfile {
+            id: self.uuid()?,
+            email: self.email()?.to_string(),
+            email_verified: self.email_verified,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserProfile {
+    id: Uuid,
+    email: String,
+    email_verified: bool,
+}
+
+// An opaque, rotating refresh token backing the short-lived access JWT.
+// Only the SHA-256 hash of the token is ever persisted; the raw value is
+// handed to the client once and never stored.
+#[derive(Debug, sqlx::FromRow, Default, Clone)]
+pub struct RefreshToken {
+    id: Option<Uuid>,
+    user_id: Option<Uuid>,
+    token_hash: Option<String>,
+    expires_at: Option<DateTime<Utc>>,
+    revoked: bool,
+}
+
+impl RefreshToken {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    pub fn with_uuid(mut self, id: Uuid) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    pub fn with_user_id(mut self, user_id: Uuid) -> Self {
+        self.user_id = Some(user_id);
+        self
+    }
+
+    pub fn with_token_hash(mut self, token_hash: String) -> Self {
+        self.token_hash = Some(token_hash);
+        self
+    }
+
+    pub fn with_expires_at(mut self, expires_at: DateTime<Utc>) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    pub fn with_revoked(mut self, revoked: bool) -> Self {
+        self.revoked = revoked;
+        self
+    }
+
+    pub fn uuid(&self) -> Result<Uuid, Error> {
+        match &self.id {
+            Some(id) => Ok(*id),
+            None => Err(Error::ValidationError("I have no UUID!".to_string())),
+        }
+    }
+
+    pub fn user_id(&self) -> Result<Uuid, Error> {
+        match &self.user_id {
+            Some(user_id) => Ok(*user_id),
+            None => Err(Error::ValidationError("I have no user_id!".to_string())),
+        }
+    }
+
+    pub fn expires_at(&self) -> Result<DateTime<Utc>, Error> {
+        match &self.expires_at {
+            Some(expires_at) => Ok(*expires_at),
+            None => Err(Error::ValidationError("I have no expires_at!".to_string())),
+        }
+    }
+
+    pub fn revoked(&self) -> bool {
+        self.revoked
+    }
+
+    // Generates a fresh 256-bit refresh token, returning the raw value to
+    // hand to the client alongside the hash that gets persisted.
+    pub fn generate() -> (String, String) {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let raw = hex::encode(bytes);
+        let hash = Self::hash(&raw);
+        (raw, hash)
+    }
+
+    pub fn hash(raw_token: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(raw_token.as_bytes());
+        hex::encode(hasher.finalize())
+    }
+
+    pub async fn create(
+        user_id: Uuid,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+        state: State<AppState>,
+    ) -> Result<RefreshToken, sqlx::Error> {
+        let token = sqlx::query_as!(
+            RefreshToken,
+            r#"
+            INSERT INTO refresh_tokens (id, user_id, token_hash, expires_at, revoked)
+            VALUES ($1, $2, $3, $4, false)
+            RETURNING id, user_id, token_hash, expires_at, revoked
+            "#,
+            Uuid::new_v4(),
+            user_id,
+            token_hash,
+            expires_at
+        )
+            .fetch_one(state.db()?)
+            .await?;
+
+        Ok(token)
+    }
+
+    pub async fn find_by_hash(token_hash: &str, state: State<AppState>) -> Result<Self, Error> {
+        sqlx::query_as!(
+            RefreshToken,
+            "SELECT id, user_id, token_hash, expires_at, revoked
+             FROM refresh_tokens WHERE token_hash = $1",
+            token_hash
+        )
+            .fetch_optional(state.db()?)
+            .await?
+            .ok_or(Error::RefreshTokenNotFound)
+    }
+
+    pub async fn revoke(id: Uuid, state: State<AppState>) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE refresh_tokens SET revoked = true WHERE id = $1",
+            id
+        )
+            .execute(state.db()?)
+            .await?;
+
+        Ok(())
+    }
+
+    // Atomically flips `revoked` from false to true. Returns `true` if this
+    // call performed the revocation, `false` if the token was already
+    // revoked by the time this ran (e.g. a concurrent rotation won the
+    // race). Callers rotating a refresh token must treat `false` as reuse.
+    pub async fn revoke_if_active(id: Uuid, state: State<AppState>) -> Result<bool, sqlx::Error> {
+        let row = sqlx::query!(
+            "UPDATE refresh_tokens SET revoked = true WHERE id = $1 AND revoked = false RETURNING id",
+            id
+        )
+            .fetch_optional(state.db()?)
+            .await?;
+
+        Ok(row.is_some())
+    }
+
+    // Revokes every outstanding refresh token for a user. Used both on
+    // logout and when a reused (already-revoked) token indicates theft.
+    pub async fn revoke_all_for_user(user_id: Uuid, state: State<AppState>) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE refresh_tokens SET revoked = true WHERE user_id = $1",
+            user_id
+        )
+            .execute(state.db()?)
+            .await?;
+
+        Ok(())
+    }
+}
+
+// A single-use token proving ownership of the email address on a user's
+// account. Like `RefreshToken`, only the SHA-256 hash is ever persisted.
+#[derive(Debug, sqlx::FromRow, Default, Clone)]
+pub struct EmailVerification {
+    token_hash: Option<String>,
+    user_id: Option<Uuid>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl EmailVerification {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    pub fn with_token_hash(mut self, token_hash: String) -> Self {
+        self.token_hash = Some(token_hash);
+        self
+    }
+
+    pub fn with_user_id(mut self, user_id: Uuid) -> Self {
+        self.user_id = Some(user_id);
+        self
+    }
+
+    pub fn with_expires_at(mut self, expires_at: DateTime<Utc>) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    pub fn user_id(&self) -> Result<Uuid, Error> {
+        match &self.user_id {
+            Some(user_id) => Ok(*user_id),
+            None => Err(Error::ValidationError("I have no user_id!".to_string())),
+        }
+    }
+
+    pub fn expires_at(&self) -> Result<DateTime<Utc>, Error> {
+        match &self.expires_at {
+            Some(expires_at) => Ok(*expires_at),
+            None => Err(Error::ValidationError("I have no expires_at!".to_string())),
+        }
+    }
+
+    // Generates a single-use verification token, returning the raw value to
+    // hand to the client (or a `Mailer`) alongside the hash that gets persisted.
+    pub fn generate() -> (String, String) {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let raw = hex::encode(bytes);
+        let hash = Self::hash(&raw);
+        (raw, hash)
+    }
+
+    pub fn hash(raw_token: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(raw_token.as_bytes());
+        hex::encode(hasher.finalize())
+    }
+
+    pub async fn create(
+        user_id: Uuid,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+        state: State<AppState>,
+    ) -> Result<EmailVerification, sqlx::Error> {
+        let verification = sqlx::query_as!(
+            EmailVerification,
+            r#"
+            INSERT INTO email_verifications (token_hash, user_id, expires_at)
+            VALUES ($1, $2, $3)
+            RETURNING token_hash, user_id, expires_at
+            "#,
+            token_hash,
+            user_id,
+            expires_at
+        )
+            .fetch_one(state.db()?)
+            .await?;
+
+        Ok(verification)
+    }
+
+    pub async fn find_by_hash(token_hash: &str, state: State<AppState>) -> Result<Self, Error> {
+        sqlx::query_as!(
+            EmailVerification,
+            "SELECT token_hash, user_id, expires_at
+             FROM email_verifications WHERE token_hash = $1",
+            token_hash
+        )
+            .fetch_optional(state.db()?)
+            .await?
+            .ok_or(Error::EmailVerificationNotFound)
+    }
+
+    pub async fn delete(token_hash: &str, state: State<AppState>) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM email_verifications WHERE token_hash = $1",
+            token_hash
+        )
+            .execute(state.db()?)
+            .await?;
+
+        Ok(())
+    }
 }